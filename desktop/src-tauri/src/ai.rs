@@ -12,6 +12,15 @@ pub struct AiRequest {
     pub max_tokens: Option<u32>,
     pub stream: bool,
     pub stream_id: String,
+    pub tools: Option<Vec<ToolDef>>,
+    /// For `vertexai`: the GCP project hosting the model.
+    pub project_id: Option<String>,
+    /// For `vertexai`: the region the model is deployed in, e.g. `us-central1`.
+    pub location: Option<String>,
+    /// For `openai-compatible`: the server root, e.g. `http://localhost:8080/v1`.
+    pub api_base: Option<String>,
+    /// Retries on 429/5xx and connect/timeout errors before any response bytes arrive. Defaults to 2.
+    pub max_retries: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -20,12 +29,208 @@ pub struct ChatMessage {
     pub content: String,
 }
 
+/// A tool/function the model may call, described with a JSON-schema `parameters` object.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ToolDef {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// A completed tool call emitted once its arguments have fully streamed in and parsed as JSON.
+#[derive(Debug, Serialize, Clone)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// Token usage and finish-state for a completion, reported once it is known.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct CompletionDetails {
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+    pub finish_reason: Option<String>,
+    /// The full concatenated text of the turn, set only on the final streamed event.
+    pub text: Option<String>,
+}
+
+/// Non-fatal heads-up that a request is being retried, so the UI can show "retrying…".
+#[derive(Debug, Serialize, Clone)]
+pub struct RetryInfo {
+    pub attempt: u32,
+    pub delay_ms: u64,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct StreamEvent {
     pub stream_id: String,
     pub delta: String,
     pub done: bool,
     pub error: Option<String>,
+    pub tool_call: Option<ToolCall>,
+    pub usage: Option<CompletionDetails>,
+    pub retry: Option<RetryInfo>,
+}
+
+impl StreamEvent {
+    fn delta(stream_id: &str, delta: String) -> Self {
+        Self { stream_id: stream_id.to_string(), delta, done: false, error: None, tool_call: None, usage: None, retry: None }
+    }
+
+    fn done(stream_id: &str) -> Self {
+        Self { stream_id: stream_id.to_string(), delta: String::new(), done: true, error: None, tool_call: None, usage: None, retry: None }
+    }
+
+    fn done_with_usage(stream_id: &str, usage: CompletionDetails) -> Self {
+        Self { stream_id: stream_id.to_string(), delta: String::new(), done: true, error: None, tool_call: None, usage: Some(usage), retry: None }
+    }
+
+    fn error(stream_id: &str, error: String) -> Self {
+        Self { stream_id: stream_id.to_string(), delta: String::new(), done: true, error: Some(error), tool_call: None, usage: None, retry: None }
+    }
+
+    fn tool_call(stream_id: &str, tool_call: ToolCall) -> Self {
+        Self { stream_id: stream_id.to_string(), delta: String::new(), done: false, error: None, tool_call: Some(tool_call), usage: None, retry: None }
+    }
+
+    fn retrying(stream_id: &str, attempt: u32, delay_ms: u64) -> Self {
+        Self {
+            stream_id: stream_id.to_string(),
+            delta: String::new(),
+            done: false,
+            error: None,
+            tool_call: None,
+            usage: None,
+            retry: Some(RetryInfo { attempt, delay_ms }),
+        }
+    }
+}
+
+/// Accumulates partial tool-call fragments across streamed chunks until each call is complete.
+#[derive(Default)]
+struct ToolCallAccumulator {
+    // OpenAI/xAI key fragments by the `index` field; several calls can interleave.
+    openai: std::collections::BTreeMap<u32, PendingToolCall>,
+    // Anthropic streams at most one `tool_use` content block at a time, tagged with its block index.
+    anthropic: Option<(u32, PendingToolCall)>,
+}
+
+struct PendingToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+fn finalize_tool_call(pending: PendingToolCall) -> Result<ToolCall, String> {
+    let arguments = if pending.arguments.trim().is_empty() {
+        serde_json::json!({})
+    } else {
+        serde_json::from_str(&pending.arguments)
+            .map_err(|e| format!("Tool call '{}' had invalid JSON arguments: {}", pending.name, e))?
+    };
+    Ok(ToolCall { id: pending.id, name: pending.name, arguments })
+}
+
+/// The fields we need out of an Application Default Credentials service-account JSON file.
+#[derive(Debug, Deserialize)]
+struct VertexServiceAccount {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+#[derive(Debug, Serialize)]
+struct VertexJwtClaims {
+    iss: String,
+    scope: String,
+    aud: String,
+    iat: i64,
+    exp: i64,
+}
+
+struct CachedVertexToken {
+    access_token: String,
+    expires_at: i64,
+}
+
+static VERTEX_TOKEN_CACHE: std::sync::OnceLock<std::sync::Mutex<std::collections::HashMap<String, CachedVertexToken>>> =
+    std::sync::OnceLock::new();
+
+fn vertex_token_cache() -> &'static std::sync::Mutex<std::collections::HashMap<String, CachedVertexToken>> {
+    VERTEX_TOKEN_CACHE.get_or_init(|| std::sync::Mutex::new(std::collections::HashMap::new()))
+}
+
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Exchanges an ADC service-account JSON blob for a short-lived OAuth access token, signing a
+/// self-issued JWT assertion and caching the result until it is within ~60s of expiring.
+async fn get_vertex_access_token(service_account_json: &str) -> Result<String, String> {
+    let creds: VertexServiceAccount = serde_json::from_str(service_account_json)
+        .map_err(|e| format!("Invalid Vertex AI service account credentials: {}", e))?;
+
+    {
+        let cache = vertex_token_cache().lock().unwrap();
+        if let Some(cached) = cache.get(&creds.client_email) {
+            if cached.expires_at - unix_now() > 60 {
+                return Ok(cached.access_token.clone());
+            }
+        }
+    }
+
+    let now = unix_now();
+    let claims = VertexJwtClaims {
+        iss: creds.client_email.clone(),
+        scope: "https://www.googleapis.com/auth/cloud-platform".to_string(),
+        aud: creds.token_uri.clone(),
+        iat: now,
+        exp: now + 3600,
+    };
+
+    let header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+    let encoding_key = jsonwebtoken::EncodingKey::from_rsa_pem(creds.private_key.as_bytes())
+        .map_err(|e| format!("Invalid Vertex AI private key: {}", e))?;
+    let assertion = jsonwebtoken::encode(&header, &claims, &encoding_key)
+        .map_err(|e| format!("Failed to sign Vertex AI JWT assertion: {}", e))?;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&creds.token_uri)
+        .form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", assertion.as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach Vertex AI token endpoint: {}", e))?;
+
+    let status = response.status();
+    let body_text = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(format!("Vertex AI token exchange failed ({}): {}", status.as_u16(), body_text));
+    }
+
+    let parsed: serde_json::Value = serde_json::from_str(&body_text)
+        .map_err(|e| format!("Failed to parse Vertex AI token response: {}", e))?;
+    let access_token = parsed
+        .get("access_token")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "Vertex AI token response missing access_token".to_string())?
+        .to_string();
+    let expires_in = parsed.get("expires_in").and_then(|v| v.as_i64()).unwrap_or(3600);
+
+    vertex_token_cache().lock().unwrap().insert(
+        creds.client_email,
+        CachedVertexToken { access_token: access_token.clone(), expires_at: now + expires_in },
+    );
+
+    Ok(access_token)
 }
 
 fn build_openai_body(request: &AiRequest) -> serde_json::Value {
@@ -51,13 +256,38 @@ fn build_openai_body(request: &AiRequest) -> serde_json::Value {
     }
     messages.extend(other_msgs);
 
-    serde_json::json!({
+    let mut body = serde_json::json!({
         "model": request.model,
         "messages": messages,
         "temperature": request.temperature.unwrap_or(0.7),
         "max_tokens": request.max_tokens.unwrap_or(4096),
         "stream": request.stream
-    })
+    });
+
+    if request.stream {
+        // Without this, usage only ever arrives on non-streamed responses.
+        body["stream_options"] = serde_json::json!({ "include_usage": true });
+    }
+
+    if let Some(tools) = &request.tools {
+        body["tools"] = serde_json::Value::Array(
+            tools
+                .iter()
+                .map(|t| {
+                    serde_json::json!({
+                        "type": "function",
+                        "function": {
+                            "name": t.name,
+                            "description": t.description,
+                            "parameters": t.parameters
+                        }
+                    })
+                })
+                .collect(),
+        );
+    }
+
+    body
 }
 
 fn build_anthropic_body(request: &AiRequest) -> serde_json::Value {
@@ -93,6 +323,21 @@ fn build_anthropic_body(request: &AiRequest) -> serde_json::Value {
         body["system"] = serde_json::Value::String(system_content);
     }
 
+    if let Some(tools) = &request.tools {
+        body["tools"] = serde_json::Value::Array(
+            tools
+                .iter()
+                .map(|t| {
+                    serde_json::json!({
+                        "name": t.name,
+                        "description": t.description,
+                        "input_schema": t.parameters
+                    })
+                })
+                .collect(),
+        );
+    }
+
     body
 }
 
@@ -136,13 +381,32 @@ fn build_gemini_body(request: &AiRequest) -> serde_json::Value {
         });
     }
 
+    if let Some(tools) = &request.tools {
+        body["tools"] = serde_json::json!([{
+            "functionDeclarations": tools.iter().map(|t| serde_json::json!({
+                "name": t.name,
+                "description": t.description,
+                "parameters": t.parameters
+            })).collect::<Vec<_>>()
+        }]);
+    }
+
     body
 }
 
-fn get_url(provider: &str, model: &str, api_key: &str, stream: bool) -> String {
+fn get_url(
+    provider: &str,
+    model: &str,
+    api_key: &str,
+    stream: bool,
+    project_id: &str,
+    location: &str,
+    api_base: &str,
+) -> String {
     match provider {
         "openai" => "https://api.openai.com/v1/chat/completions".to_string(),
         "xai" => "https://api.x.ai/v1/chat/completions".to_string(),
+        "openai-compatible" => format!("{}/chat/completions", api_base.trim_end_matches('/')),
         "anthropic" => "https://api.anthropic.com/v1/messages".to_string(),
         "gemini" => {
             if stream {
@@ -157,6 +421,17 @@ fn get_url(provider: &str, model: &str, api_key: &str, stream: bool) -> String {
                 )
             }
         }
+        "vertexai" => {
+            let method = if stream { "streamGenerateContent" } else { "generateContent" };
+            let url = format!(
+                "https://{location}-aiplatform.googleapis.com/v1/projects/{project_id}/locations/{location}/publishers/google/models/{model}:{method}"
+            );
+            if stream {
+                format!("{}?alt=sse", url)
+            } else {
+                url
+            }
+        }
         _ => String::new(),
     }
 }
@@ -185,7 +460,7 @@ fn map_error(status: u16, provider: &str, model: &str, body_text: &str) -> Strin
 
 fn extract_non_stream_content(provider: &str, body: &serde_json::Value) -> Result<String, String> {
     match provider {
-        "openai" | "xai" => body
+        "openai" | "xai" | "openai-compatible" => body
             .get("choices")
             .and_then(|c| c.get(0))
             .and_then(|c| c.get("message"))
@@ -200,7 +475,7 @@ fn extract_non_stream_content(provider: &str, body: &serde_json::Value) -> Resul
             .and_then(|t| t.as_str())
             .map(|s| s.to_string())
             .ok_or_else(|| "Failed to parse Anthropic response".to_string()),
-        "gemini" => body
+        "gemini" | "vertexai" => body
             .get("candidates")
             .and_then(|c| c.get(0))
             .and_then(|c| c.get("content"))
@@ -214,11 +489,81 @@ fn extract_non_stream_content(provider: &str, body: &serde_json::Value) -> Resul
     }
 }
 
+/// Parses the tool calls out of a non-streamed response body, if the model invoked any instead
+/// of (or alongside) returning text. Returns an empty vec for a plain text response.
+fn extract_non_stream_tool_calls(provider: &str, body: &serde_json::Value) -> Result<Vec<ToolCall>, String> {
+    match provider {
+        "openai" | "xai" | "openai-compatible" => {
+            let Some(calls) = body
+                .get("choices")
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("message"))
+                .and_then(|m| m.get("tool_calls"))
+                .and_then(|t| t.as_array())
+            else {
+                return Ok(Vec::new());
+            };
+            calls
+                .iter()
+                .map(|call| {
+                    let id = call.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                    let function = call.get("function");
+                    let name = function
+                        .and_then(|f| f.get("name"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string();
+                    let arguments_str = function.and_then(|f| f.get("arguments")).and_then(|v| v.as_str()).unwrap_or("{}");
+                    let arguments = serde_json::from_str(arguments_str)
+                        .map_err(|e| format!("Tool call '{}' had invalid JSON arguments: {}", name, e))?;
+                    Ok(ToolCall { id, name, arguments })
+                })
+                .collect()
+        }
+        "anthropic" => {
+            let Some(content) = body.get("content").and_then(|c| c.as_array()) else {
+                return Ok(Vec::new());
+            };
+            content
+                .iter()
+                .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+                .map(|block| {
+                    let id = block.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                    let name = block.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                    let arguments = block.get("input").cloned().unwrap_or_else(|| serde_json::json!({}));
+                    Ok(ToolCall { id, name, arguments })
+                })
+                .collect()
+        }
+        "gemini" | "vertexai" => {
+            let Some(parts) = body
+                .get("candidates")
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("content"))
+                .and_then(|c| c.get("parts"))
+                .and_then(|p| p.as_array())
+            else {
+                return Ok(Vec::new());
+            };
+            Ok(parts
+                .iter()
+                .filter_map(|p| p.get("functionCall"))
+                .map(|fc| {
+                    let name = fc.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                    let arguments = fc.get("args").cloned().unwrap_or_else(|| serde_json::json!({}));
+                    ToolCall { id: name.clone(), name, arguments }
+                })
+                .collect())
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
 fn extract_stream_delta(provider: &str, data: &str) -> Option<String> {
     let parsed: serde_json::Value = serde_json::from_str(data).ok()?;
 
     match provider {
-        "openai" | "xai" => parsed
+        "openai" | "xai" | "openai-compatible" => parsed
             .get("choices")
             .and_then(|c| c.get(0))
             .and_then(|c| c.get("delta"))
@@ -236,7 +581,7 @@ fn extract_stream_delta(provider: &str, data: &str) -> Option<String> {
                 _ => None,
             }
         }
-        "gemini" => parsed
+        "gemini" | "vertexai" => parsed
             .get("candidates")
             .and_then(|c| c.get(0))
             .and_then(|c| c.get("content"))
@@ -255,21 +600,18 @@ fn is_stream_done(provider: &str, data: &str) -> bool {
     }
     if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(data) {
         match provider {
-            "openai" | "xai" => {
-                if let Some(choices) = parsed.get("choices").and_then(|c| c.as_array()) {
-                    if let Some(choice) = choices.first() {
-                        if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
-                            return reason == "stop" || reason == "end_turn";
-                        }
-                    }
-                }
-            }
+            // With `stream_options.include_usage`, OpenAI/xAI send the `finish_reason` chunk
+            // with `usage: null` followed by a trailing `choices: []` chunk carrying the real
+            // usage object, then `[DONE]`. Ending the loop on `finish_reason` would drop that
+            // usage chunk, so for this family only the literal `[DONE]` sentinel (handled
+            // above) ends the stream; `finish_reason` is merged into `usage` instead.
+            "openai" | "xai" | "openai-compatible" => {}
             "anthropic" => {
                 if let Some(t) = parsed.get("type").and_then(|t| t.as_str()) {
                     return t == "message_stop";
                 }
             }
-            "gemini" => {
+            "gemini" | "vertexai" => {
                 if let Some(candidates) = parsed.get("candidates").and_then(|c| c.as_array()) {
                     if let Some(candidate) = candidates.first() {
                         if let Some(reason) = candidate.get("finishReason").and_then(|r| r.as_str()) {
@@ -284,58 +626,231 @@ fn is_stream_done(provider: &str, data: &str) -> bool {
     false
 }
 
+/// Merges whatever usage/finish-reason fields a single provider payload carries into `usage`.
+/// Called once per non-stream response body, and once per streamed event, so it only overwrites
+/// fields the payload actually has (providers spread usage across several events).
+fn merge_usage(provider: &str, parsed: &serde_json::Value, usage: &mut CompletionDetails) {
+    match provider {
+        "openai" | "xai" | "openai-compatible" => {
+            if let Some(u) = parsed.get("usage") {
+                if let Some(v) = u.get("prompt_tokens").and_then(|v| v.as_u64()) { usage.prompt_tokens = Some(v as u32); }
+                if let Some(v) = u.get("completion_tokens").and_then(|v| v.as_u64()) { usage.completion_tokens = Some(v as u32); }
+                if let Some(v) = u.get("total_tokens").and_then(|v| v.as_u64()) { usage.total_tokens = Some(v as u32); }
+            }
+            if let Some(reason) = parsed
+                .get("choices")
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("finish_reason"))
+                .and_then(|r| r.as_str())
+            {
+                usage.finish_reason = Some(reason.to_string());
+            }
+        }
+        "anthropic" => {
+            let event_type = parsed.get("type").and_then(|t| t.as_str()).unwrap_or("");
+            match event_type {
+                "message_start" => {
+                    if let Some(input_tokens) = parsed
+                        .get("message")
+                        .and_then(|m| m.get("usage"))
+                        .and_then(|u| u.get("input_tokens"))
+                        .and_then(|v| v.as_u64())
+                    {
+                        usage.prompt_tokens = Some(input_tokens as u32);
+                    }
+                }
+                "message_delta" => {
+                    if let Some(output_tokens) =
+                        parsed.get("usage").and_then(|u| u.get("output_tokens")).and_then(|v| v.as_u64())
+                    {
+                        usage.completion_tokens = Some(output_tokens as u32);
+                    }
+                    if let Some(reason) = parsed.get("delta").and_then(|d| d.get("stop_reason")).and_then(|r| r.as_str()) {
+                        usage.finish_reason = Some(reason.to_string());
+                    }
+                }
+                // Non-streaming Anthropic responses carry both counts directly on the body.
+                _ => {
+                    if let Some(input_tokens) =
+                        parsed.get("usage").and_then(|u| u.get("input_tokens")).and_then(|v| v.as_u64())
+                    {
+                        usage.prompt_tokens = Some(input_tokens as u32);
+                    }
+                    if let Some(output_tokens) =
+                        parsed.get("usage").and_then(|u| u.get("output_tokens")).and_then(|v| v.as_u64())
+                    {
+                        usage.completion_tokens = Some(output_tokens as u32);
+                    }
+                    if let Some(reason) = parsed.get("stop_reason").and_then(|r| r.as_str()) {
+                        usage.finish_reason = Some(reason.to_string());
+                    }
+                }
+            }
+            if let (Some(p), Some(c)) = (usage.prompt_tokens, usage.completion_tokens) {
+                usage.total_tokens = Some(p + c);
+            }
+        }
+        "gemini" | "vertexai" => {
+            if let Some(u) = parsed.get("usageMetadata") {
+                if let Some(v) = u.get("promptTokenCount").and_then(|v| v.as_u64()) { usage.prompt_tokens = Some(v as u32); }
+                if let Some(v) = u.get("candidatesTokenCount").and_then(|v| v.as_u64()) { usage.completion_tokens = Some(v as u32); }
+                if let Some(v) = u.get("totalTokenCount").and_then(|v| v.as_u64()) { usage.total_tokens = Some(v as u32); }
+            }
+            if let Some(reason) = parsed
+                .get("candidates")
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("finishReason"))
+                .and_then(|r| r.as_str())
+            {
+                usage.finish_reason = Some(reason.to_string());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The result of a non-streamed `ai_call`; streamed calls return the same shape once the
+/// stream finishes, with `content` holding the concatenation of every emitted delta.
+#[derive(Debug, Serialize, Clone)]
+pub struct AiCallResult {
+    pub content: String,
+    pub usage: CompletionDetails,
+    /// Tool calls from a non-streamed response. Streamed tool calls are emitted as `StreamEvent`s
+    /// instead, so this is always empty for `request.stream == true`.
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// Exponential backoff with jitter, in case a provider doesn't send `Retry-After`.
+fn backoff_with_jitter(attempt: u32) -> std::time::Duration {
+    let base_ms = 500u64.saturating_mul(1u64 << attempt.min(10));
+    let jitter_seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    let jitter_ms = jitter_seed % (base_ms / 2 + 1);
+    std::time::Duration::from_millis(base_ms + jitter_ms)
+}
+
+/// Parses a `Retry-After` header, which providers send either as a number of seconds or an
+/// HTTP-date.
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<std::time::Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(std::time::Duration::from_secs(seconds));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    at.duration_since(std::time::SystemTime::now()).ok()
+}
+
+/// Sends a request, retrying on 429/5xx responses and on connect/timeout errors, up to
+/// `max_retries` times with exponential backoff (honoring `Retry-After` when present). Only
+/// covers the window before any response bytes have arrived, so streamed output already sent
+/// to the frontend is never retried or duplicated.
+async fn send_with_retry(
+    build_request: impl Fn() -> reqwest::RequestBuilder,
+    max_retries: u32,
+    provider: &str,
+    app: &tauri::AppHandle,
+    stream_id: &str,
+) -> Result<reqwest::Response, String> {
+    let mut attempt = 0u32;
+    loop {
+        match build_request().send().await {
+            Ok(response) => {
+                let status = response.status().as_u16();
+                let retryable = status == 429 || (500..=599).contains(&status);
+                if retryable && attempt < max_retries {
+                    let delay = parse_retry_after(response.headers()).unwrap_or_else(|| backoff_with_jitter(attempt));
+                    attempt += 1;
+                    let _ = app.emit("ai-stream", StreamEvent::retrying(stream_id, attempt, delay.as_millis() as u64));
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                return Ok(response);
+            }
+            Err(e) => {
+                let is_connect_or_timeout = e.is_connect() || e.is_timeout();
+                if is_connect_or_timeout && attempt < max_retries {
+                    let delay = backoff_with_jitter(attempt);
+                    attempt += 1;
+                    let _ = app.emit("ai-stream", StreamEvent::retrying(stream_id, attempt, delay.as_millis() as u64));
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+                return Err(if e.is_timeout() {
+                    format!("Request to {} timed out.", provider)
+                } else if e.is_connect() {
+                    format!("Cannot reach {}. Check your internet connection.", provider)
+                } else {
+                    format!("Network error connecting to {}: {}", provider, e)
+                });
+            }
+        }
+    }
+}
+
 #[tauri::command]
-pub async fn ai_call(app: tauri::AppHandle, request: AiRequest) -> Result<String, String> {
+pub async fn ai_call(app: tauri::AppHandle, request: AiRequest) -> Result<AiCallResult, String> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(300))
         .build()
         .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
 
-    let url = get_url(&request.provider, &request.model, &request.api_key, request.stream);
+    if request.provider == "vertexai" && (request.project_id.is_none() || request.location.is_none()) {
+        return Err("Vertex AI requires project_id and location".to_string());
+    }
+    if request.provider == "openai-compatible" && request.api_base.is_none() {
+        return Err("openai-compatible provider requires api_base".to_string());
+    }
+
+    let url = get_url(
+        &request.provider,
+        &request.model,
+        &request.api_key,
+        request.stream,
+        request.project_id.as_deref().unwrap_or(""),
+        request.location.as_deref().unwrap_or(""),
+        request.api_base.as_deref().unwrap_or(""),
+    );
     if url.is_empty() {
         return Err(format!("Unknown provider: {}", request.provider));
     }
 
     let body = match request.provider.as_str() {
-        "openai" | "xai" => build_openai_body(&request),
+        "openai" | "xai" | "openai-compatible" => build_openai_body(&request),
         "anthropic" => build_anthropic_body(&request),
-        "gemini" => build_gemini_body(&request),
+        "gemini" | "vertexai" => build_gemini_body(&request),
         _ => return Err(format!("Unknown provider: {}", request.provider)),
     };
 
-    let mut req_builder = client.post(&url).header("Content-Type", "application/json");
+    // Vertex AI trades the API key for a short-lived OAuth token before headers are built.
+    let vertex_access_token = if request.provider == "vertexai" {
+        Some(get_vertex_access_token(&request.api_key).await.map_err(|e| format!("Vertex AI auth failed: {}", e))?)
+    } else {
+        None
+    };
 
-    match request.provider.as_str() {
-        "openai" => {
-            req_builder = req_builder.header("Authorization", format!("Bearer {}", request.api_key));
-        }
-        "xai" => {
-            req_builder = req_builder.header("Authorization", format!("Bearer {}", request.api_key));
-        }
-        "anthropic" => {
-            req_builder = req_builder
+    let build_request = || {
+        let mut req_builder = client.post(&url).header("Content-Type", "application/json");
+        req_builder = match request.provider.as_str() {
+            "openai" | "xai" | "openai-compatible" => {
+                req_builder.header("Authorization", format!("Bearer {}", request.api_key))
+            }
+            "anthropic" => req_builder
                 .header("x-api-key", &request.api_key)
-                .header("anthropic-version", "2023-06-01");
-        }
-        "gemini" => {
-            // API key is in the URL query parameter
-        }
-        _ => {}
-    }
+                .header("anthropic-version", "2023-06-01"),
+            "gemini" => req_builder, // API key is in the URL query parameter
+            "vertexai" => req_builder.header(
+                "Authorization",
+                format!("Bearer {}", vertex_access_token.as_deref().unwrap_or_default()),
+            ),
+            _ => req_builder,
+        };
+        req_builder.json(&body)
+    };
 
-    let response = req_builder
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                format!("Request to {} timed out.", request.provider)
-            } else if e.is_connect() {
-                format!("Cannot reach {}. Check your internet connection.", request.provider)
-            } else {
-                format!("Network error connecting to {}: {}", request.provider, e)
-            }
-        })?;
+    let max_retries = request.max_retries.unwrap_or(2);
+    let response = send_with_retry(build_request, max_retries, &request.provider, &app, &request.stream_id).await?;
 
     let status = response.status().as_u16();
 
@@ -349,19 +864,23 @@ pub async fn ai_call(app: tauri::AppHandle, request: AiRequest) -> Result<String
         let parsed: serde_json::Value = serde_json::from_str(&body_text)
             .map_err(|e| format!("Failed to parse response JSON: {}", e))?;
 
-        return extract_non_stream_content(&request.provider, &parsed);
+        let tool_calls = extract_non_stream_tool_calls(&request.provider, &parsed)?;
+        let content = if tool_calls.is_empty() {
+            extract_non_stream_content(&request.provider, &parsed)?
+        } else {
+            extract_non_stream_content(&request.provider, &parsed).unwrap_or_default()
+        };
+        let mut usage = CompletionDetails::default();
+        merge_usage(&request.provider, &parsed, &mut usage);
+
+        return Ok(AiCallResult { content, usage, tool_calls });
     }
 
     // Streaming mode
     if status < 200 || status >= 300 {
         let body_text = response.text().await.unwrap_or_default();
         let error_msg = map_error(status, &request.provider, &request.model, &body_text);
-        let _ = app.emit("ai-stream", StreamEvent {
-            stream_id: request.stream_id.clone(),
-            delta: String::new(),
-            done: true,
-            error: Some(error_msg.clone()),
-        });
+        let _ = app.emit("ai-stream", StreamEvent::error(&request.stream_id, error_msg.clone()));
         return Err(error_msg);
     }
 
@@ -369,74 +888,287 @@ pub async fn ai_call(app: tauri::AppHandle, request: AiRequest) -> Result<String
     let provider = request.provider.clone();
 
     let mut byte_stream = response.bytes_stream();
-    let mut buffer = String::new();
+    // Raw bytes awaiting a complete line; a multi-byte UTF-8 character split across network
+    // chunks lives here until the rest of it arrives, so we never decode a partial character.
+    let mut byte_buffer: Vec<u8> = Vec::new();
+    // `data:` lines accumulated for the SSE event currently being assembled, joined with '\n'
+    // and flushed once a blank line closes the event (or the stream ends without one).
+    let mut pending_event_lines: Vec<String> = Vec::new();
+    let mut tool_calls = ToolCallAccumulator::default();
+    let mut usage = CompletionDetails::default();
+    let mut full_text = String::new();
 
     while let Some(chunk_result) = byte_stream.next().await {
         match chunk_result {
             Ok(bytes) => {
-                let text = String::from_utf8_lossy(&bytes);
-                buffer.push_str(&text);
+                byte_buffer.extend_from_slice(&bytes);
 
-                // Process complete lines from buffer
-                while let Some(newline_pos) = buffer.find('\n') {
-                    let line = buffer[..newline_pos].trim().to_string();
-                    buffer = buffer[newline_pos + 1..].to_string();
+                while let Some(newline_pos) = byte_buffer.iter().position(|&b| b == b'\n') {
+                    let line_bytes: Vec<u8> = byte_buffer.drain(..=newline_pos).collect();
+                    let line = String::from_utf8_lossy(&line_bytes[..line_bytes.len() - 1])
+                        .trim_end_matches('\r')
+                        .to_string();
 
                     if line.is_empty() {
+                        if pending_event_lines.is_empty() {
+                            continue;
+                        }
+                        let data = pending_event_lines.join("\n");
+                        pending_event_lines.clear();
+                        match process_sse_event(&provider, &data, &mut tool_calls, &mut usage, &mut full_text, &app, &stream_id) {
+                            Ok(SseOutcome::Done) => return Ok(AiCallResult { content: full_text, usage, tool_calls: Vec::new() }),
+                            Ok(SseOutcome::Continue) => {}
+                            Err(e) => return Err(e),
+                        }
                         continue;
                     }
 
-                    let data = if line.starts_with("data: ") {
-                        line[6..].to_string()
-                    } else if line.starts_with("data:") {
-                        line[5..].trim().to_string()
-                    } else {
-                        // Skip non-data lines (event:, id:, retry:, etc.)
-                        continue;
-                    };
-
-                    if is_stream_done(&provider, &data) {
-                        let _ = app.emit("ai-stream", StreamEvent {
-                            stream_id: stream_id.clone(),
-                            delta: String::new(),
-                            done: true,
-                            error: None,
-                        });
-                        return Ok(String::new());
-                    }
-
-                    if let Some(delta) = extract_stream_delta(&provider, &data) {
-                        if !delta.is_empty() {
-                            let _ = app.emit("ai-stream", StreamEvent {
-                                stream_id: stream_id.clone(),
-                                delta,
-                                done: false,
-                                error: None,
-                            });
-                        }
+                    if let Some(rest) = line.strip_prefix("data:") {
+                        pending_event_lines.push(rest.strip_prefix(' ').unwrap_or(rest).to_string());
                     }
+                    // Other SSE fields (event:, id:, retry:, comments) carry no payload we need.
                 }
             }
             Err(e) => {
                 let error_msg = format!("Stream error from {}: {}", provider, e);
-                let _ = app.emit("ai-stream", StreamEvent {
-                    stream_id: stream_id.clone(),
-                    delta: String::new(),
-                    done: true,
-                    error: Some(error_msg.clone()),
-                });
+                let _ = app.emit("ai-stream", StreamEvent::error(&stream_id, error_msg.clone()));
                 return Err(error_msg);
             }
         }
     }
 
+    // The stream can end without a trailing blank line; flush whatever event was in progress.
+    if !pending_event_lines.is_empty() {
+        let data = pending_event_lines.join("\n");
+        match process_sse_event(&provider, &data, &mut tool_calls, &mut usage, &mut full_text, &app, &stream_id) {
+            Ok(SseOutcome::Done) => return Ok(AiCallResult { content: full_text, usage, tool_calls: Vec::new() }),
+            Ok(SseOutcome::Continue) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
     // Stream ended naturally
-    let _ = app.emit("ai-stream", StreamEvent {
-        stream_id: stream_id.clone(),
-        delta: String::new(),
-        done: true,
-        error: None,
-    });
+    usage.text = Some(full_text.clone());
+    let _ = app.emit("ai-stream", StreamEvent::done_with_usage(&stream_id, usage.clone()));
+
+    Ok(AiCallResult { content: full_text, usage, tool_calls: Vec::new() })
+}
+
+enum SseOutcome {
+    Continue,
+    Done,
+}
+
+/// Runs one fully-assembled SSE event's `data` payload through tool-call accumulation, usage
+/// merging, completion detection, and delta extraction, emitting the corresponding `StreamEvent`s.
+fn process_sse_event(
+    provider: &str,
+    data: &str,
+    tool_calls: &mut ToolCallAccumulator,
+    usage: &mut CompletionDetails,
+    full_text: &mut String,
+    app: &tauri::AppHandle,
+    stream_id: &str,
+) -> Result<SseOutcome, String> {
+    if let Err(e) = handle_tool_call_fragment(provider, data, tool_calls, app, stream_id) {
+        let _ = app.emit("ai-stream", StreamEvent::error(stream_id, e.clone()));
+        return Err(e);
+    }
+
+    if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(data) {
+        merge_usage(provider, &parsed, usage);
+    }
+
+    if is_stream_done(provider, data) {
+        usage.text = Some(full_text.clone());
+        let _ = app.emit("ai-stream", StreamEvent::done_with_usage(stream_id, usage.clone()));
+        return Ok(SseOutcome::Done);
+    }
+
+    if let Some(delta) = extract_stream_delta(provider, data) {
+        if !delta.is_empty() {
+            full_text.push_str(&delta);
+            let _ = app.emit("ai-stream", StreamEvent::delta(stream_id, delta));
+        }
+    }
+
+    Ok(SseOutcome::Continue)
+}
+
+/// Feeds one SSE data payload into the tool-call accumulator, emitting a `ToolCall` event
+/// for each call that completes (its arguments fully received and parsed as JSON).
+fn handle_tool_call_fragment(
+    provider: &str,
+    data: &str,
+    acc: &mut ToolCallAccumulator,
+    app: &tauri::AppHandle,
+    stream_id: &str,
+) -> Result<(), String> {
+    let parsed: serde_json::Value = match serde_json::from_str(data) {
+        Ok(v) => v,
+        Err(_) => return Ok(()),
+    };
+
+    match provider {
+        "openai" | "xai" | "openai-compatible" => {
+            let choice = parsed.get("choices").and_then(|c| c.get(0));
+            if let Some(delta) = choice.and_then(|c| c.get("delta")) {
+                if let Some(fragments) = delta.get("tool_calls").and_then(|t| t.as_array()) {
+                    for frag in fragments {
+                        let Some(index) = frag.get("index").and_then(|i| i.as_u64()).map(|i| i as u32) else {
+                            continue;
+                        };
+                        let entry = acc.openai.entry(index).or_insert_with(|| PendingToolCall {
+                            id: String::new(),
+                            name: String::new(),
+                            arguments: String::new(),
+                        });
+                        if let Some(id) = frag.get("id").and_then(|v| v.as_str()) {
+                            entry.id = id.to_string();
+                        }
+                        if let Some(function) = frag.get("function") {
+                            if let Some(name) = function.get("name").and_then(|v| v.as_str()) {
+                                entry.name = name.to_string();
+                            }
+                            if let Some(args) = function.get("arguments").and_then(|v| v.as_str()) {
+                                entry.arguments.push_str(args);
+                            }
+                        }
+                    }
+                }
+            }
+            let finish_reason = choice.and_then(|c| c.get("finish_reason")).and_then(|r| r.as_str());
+            if finish_reason == Some("tool_calls") {
+                for (_, pending) in std::mem::take(&mut acc.openai) {
+                    let tool_call = finalize_tool_call(pending)?;
+                    let _ = app.emit("ai-stream", StreamEvent::tool_call(stream_id, tool_call));
+                }
+            }
+        }
+        "anthropic" => {
+            let event_type = parsed.get("type").and_then(|t| t.as_str()).unwrap_or("");
+            match event_type {
+                "content_block_start" => {
+                    let block = parsed.get("content_block");
+                    if block.and_then(|b| b.get("type")).and_then(|t| t.as_str()) == Some("tool_use") {
+                        let index = parsed.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as u32;
+                        let id = block
+                            .and_then(|b| b.get("id"))
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        let name = block
+                            .and_then(|b| b.get("name"))
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        acc.anthropic = Some((index, PendingToolCall { id, name, arguments: String::new() }));
+                    }
+                }
+                "content_block_delta" => {
+                    if let Some(partial) = parsed
+                        .get("delta")
+                        .and_then(|d| d.get("partial_json"))
+                        .and_then(|v| v.as_str())
+                    {
+                        if let Some((_, pending)) = acc.anthropic.as_mut() {
+                            pending.arguments.push_str(partial);
+                        }
+                    }
+                }
+                "content_block_stop" => {
+                    let index = parsed.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as u32;
+                    if matches!(&acc.anthropic, Some((i, _)) if *i == index) {
+                        if let Some((_, pending)) = acc.anthropic.take() {
+                            let tool_call = finalize_tool_call(pending)?;
+                            let _ = app.emit("ai-stream", StreamEvent::tool_call(stream_id, tool_call));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        "gemini" | "vertexai" => {
+            let parts = parsed
+                .get("candidates")
+                .and_then(|c| c.get(0))
+                .and_then(|c| c.get("content"))
+                .and_then(|c| c.get("parts"))
+                .and_then(|p| p.as_array());
+            for function_call in parts.into_iter().flatten().filter_map(|p| p.get("functionCall")) {
+                let name = function_call.get("name").and_then(|v| v.as_str()).unwrap_or_default();
+                let args = function_call.get("args").cloned().unwrap_or_else(|| serde_json::json!({}));
+                let tool_call = ToolCall { id: name.to_string(), name: name.to_string(), arguments: args };
+                let _ = app.emit("ai-stream", StreamEvent::tool_call(stream_id, tool_call));
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// One model entry in an arena run: everything `AiRequest` needs except the shared `messages`.
+#[derive(Debug, Deserialize)]
+pub struct ArenaTarget {
+    pub provider: String,
+    pub api_key: String,
+    pub model: String,
+    pub stream_id: String,
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u32>,
+    pub tools: Option<Vec<ToolDef>>,
+    pub project_id: Option<String>,
+    pub location: Option<String>,
+    pub api_base: Option<String>,
+    pub max_retries: Option<u32>,
+}
+
+/// Streams the same prompt to several models at once for side-by-side comparison. Each target
+/// gets its own task and its own `stream_id`, so the frontend can render one column per model;
+/// one model erroring out emits only that model's error event and never affects the others.
+#[tauri::command]
+pub async fn ai_call_arena(
+    app: tauri::AppHandle,
+    targets: Vec<ArenaTarget>,
+    messages: Vec<ChatMessage>,
+) -> Result<(), String> {
+    let mut handles = Vec::new();
+
+    for target in targets {
+        let app = app.clone();
+        let messages = messages.clone();
+
+        handles.push(tauri::async_runtime::spawn(async move {
+            let stream_id = target.stream_id.clone();
+            let request = AiRequest {
+                provider: target.provider,
+                api_key: target.api_key,
+                model: target.model,
+                messages,
+                temperature: target.temperature,
+                max_tokens: target.max_tokens,
+                stream: true,
+                stream_id: stream_id.clone(),
+                tools: target.tools,
+                project_id: target.project_id,
+                location: target.location,
+                api_base: target.api_base,
+                max_retries: target.max_retries,
+            };
+
+            // ai_call returns some errors (bad config, connect errors) without emitting an
+            // "ai-stream" event, so every error is emitted here; failures that ai_call already
+            // emitted (HTTP error status, stream errors, invalid tool-call JSON) arrive twice.
+            if let Err(e) = ai_call(app.clone(), request).await {
+                let _ = app.emit("ai-stream", StreamEvent::error(&stream_id, e));
+            }
+        }));
+    }
+
+    for handle in handles {
+        let _ = handle.await;
+    }
 
-    Ok(String::new())
+    Ok(())
 }